@@ -0,0 +1,147 @@
+//! Parsing of the `---`-delimited YAML front matter block that may appear
+//! at the top of a markdown source file
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+static FENCE: &str = "---";
+
+/// Metadata extracted from a markdown file's front matter block
+#[derive(Debug, Default, Clone)]
+pub struct FrontMatter {
+    values: HashMap<String, String>,
+}
+
+impl FrontMatter {
+    /// Read and parse only the front matter block of the markdown file at
+    /// `path`, without converting its body. Used to peek at metadata (e.g.
+    /// `draft`) for files that haven't gone through `InputPaths::parse_as_html` yet.
+    pub fn read_from_file(path: &Path) -> io::Result<Self> {
+        let mut buffer = String::new();
+        File::open(path)?.read_to_string(&mut buffer)?;
+        Ok(Self::extract(&buffer).0)
+    }
+
+    /// Split `content` into a parsed front matter block and the remaining
+    /// markdown body.
+    ///
+    /// If `content` doesn't start with a `---` fence, an empty
+    /// `FrontMatter` is returned alongside the untouched content.
+    pub fn extract(content: &str) -> (Self, &str) {
+        if !content.starts_with(FENCE) {
+            return (Self::default(), content);
+        }
+
+        let rest = content[FENCE.len()..].trim_start_matches('\r').trim_start_matches('\n');
+
+        match rest.find(FENCE) {
+            Some(end) => {
+                let block = &rest[..end];
+                let body = rest[end + FENCE.len()..]
+                    .trim_start_matches('\r')
+                    .trim_start_matches('\n');
+                (Self::from_block(block), body)
+            },
+            None => (Self::default(), content),
+        }
+    }
+
+    fn from_block(block: &str) -> Self {
+        let mut values = HashMap::new();
+        for line in block.lines() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(sep) = line.find(':') {
+                let key = line[..sep].trim().to_string();
+                let value = line[sep + 1..].trim().trim_matches('"').to_string();
+                values.insert(key, value);
+            }
+        }
+
+        FrontMatter { values: values }
+    }
+
+    /// Look up a front matter value by key
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(|v| v.as_str())
+    }
+
+    /// Whether this file is marked `draft: true` and should be skipped
+    pub fn is_draft(&self) -> bool {
+        self.get("draft").map_or(false, |v| v == "true")
+    }
+
+    /// The configured output permalink, if any, overriding the default
+    /// `.md` -> `.html` path derivation
+    pub fn permalink(&self) -> Option<&str> {
+        self.get("permalink")
+    }
+
+    /// The template layout requested for this file, defaulting to `"default"`
+    pub fn layout(&self) -> &str {
+        self.get("layout").unwrap_or("default")
+    }
+
+    /// The maximum heading level to include in the table of contents,
+    /// defaulting to `6` (i.e. every heading)
+    pub fn toc_max_depth(&self) -> u8 {
+        self.get("toc_max_depth").and_then(|v| v.parse().ok()).unwrap_or(6)
+    }
+
+    /// Iterate over all key/value pairs, e.g. for template substitution
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.values.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracts_front_matter_and_leaves_the_body_untouched() {
+        let content = "---\ntitle: Hello\nlayout: page\n---\n# Hi\n";
+        let (front_matter, body) = FrontMatter::extract(content);
+
+        assert_eq!(front_matter.get("title"), Some("Hello"));
+        assert_eq!(front_matter.layout(), "page");
+        assert_eq!(body, "# Hi\n");
+    }
+
+    #[test]
+    fn content_without_a_leading_fence_has_no_front_matter() {
+        let content = "# Just a heading\n";
+        let (front_matter, body) = FrontMatter::extract(content);
+
+        assert_eq!(front_matter.get("title"), None);
+        assert_eq!(body, content);
+    }
+
+    #[test]
+    fn an_unterminated_fence_is_treated_as_having_no_front_matter() {
+        let content = "---\ntitle: Hello\n# No closing fence\n";
+        let (front_matter, body) = FrontMatter::extract(content);
+
+        assert_eq!(front_matter.get("title"), None);
+        assert_eq!(body, content);
+    }
+
+    #[test]
+    fn draft_is_only_true_for_the_literal_string_true() {
+        assert!(FrontMatter::extract("---\ndraft: true\n---\n").0.is_draft());
+        assert!(!FrontMatter::extract("---\ndraft: false\n---\n").0.is_draft());
+        assert!(!FrontMatter::extract("# No front matter\n").0.is_draft());
+    }
+
+    #[test]
+    fn layout_and_toc_max_depth_fall_back_to_their_defaults() {
+        let front_matter = FrontMatter::default();
+        assert_eq!(front_matter.layout(), "default");
+        assert_eq!(front_matter.toc_max_depth(), 6);
+    }
+}
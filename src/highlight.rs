@@ -0,0 +1,134 @@
+//! Syntax highlighting for fenced code blocks
+//!
+//! Runs as a post-processing step over already-converted HTML, locating
+//! `<pre><code class="...">...</code></pre>` blocks the markdown converter
+//! emits for a fenced block's language tag and replacing them with
+//! syntect's highlighted, class-annotated markup. Both the CommonMark-style
+//! `language-X` class and a bare `X` class are recognized, since which one
+//! a given markdown converter emits isn't guaranteed. Languages syntect has
+//! no syntax for are left as plain, unhighlighted blocks.
+
+use syntect::parsing::SyntaxSet;
+use syntect::highlighting::ThemeSet;
+use syntect::html::highlighted_html_for_string;
+
+static OPEN_PREFIX: &str = "<pre><code class=\"";
+static LANGUAGE_PREFIX: &str = "language-";
+static CLOSE_TAG: &str = "</code></pre>";
+
+/// Highlights fenced code blocks using a configurable theme and the set of
+/// syntaxes bundled with syntect
+pub struct Highlighter {
+    syntax_set: SyntaxSet,
+    theme_set: ThemeSet,
+    theme_name: String,
+}
+
+impl Highlighter {
+    /// Build a highlighter using the default bundled syntax definitions and
+    /// the theme named `theme_name`, falling back to `"InspiredGitHub"` if
+    /// it isn't one of the bundled themes
+    pub fn new(theme_name: &str) -> Self {
+        Highlighter {
+            syntax_set: SyntaxSet::load_defaults_newlines(),
+            theme_set: ThemeSet::load_defaults(),
+            theme_name: theme_name.to_owned(),
+        }
+    }
+
+    /// Replace every fenced code block in `html` with a syntax-highlighted
+    /// rendering
+    pub fn highlight_code_blocks(&self, html: &str) -> String {
+        let mut output = String::with_capacity(html.len());
+        let mut rest = html;
+
+        while let Some(start) = rest.find(OPEN_PREFIX) {
+            output.push_str(&rest[..start]);
+            let tail = &rest[start..];
+            let after_prefix = &tail[OPEN_PREFIX.len()..];
+
+            match (after_prefix.find('"'), tail.find(CLOSE_TAG)) {
+                (Some(class_end), Some(close_at)) => {
+                    let class = &after_prefix[..class_end];
+                    let language = class.trim_start_matches(LANGUAGE_PREFIX);
+                    let body_start = after_prefix[class_end..].find('>')
+                        .map(|i| OPEN_PREFIX.len() + class_end + i + 1);
+
+                    match body_start {
+                        Some(body_start) if body_start <= close_at => {
+                            let code = &tail[body_start..close_at];
+                            match self.highlight_block(language, code) {
+                                Some(highlighted) => output.push_str(&highlighted),
+                                None => output.push_str(&tail[..close_at + CLOSE_TAG.len()]),
+                            }
+                            rest = &tail[close_at + CLOSE_TAG.len()..];
+                        },
+                        _ => {
+                            output.push_str(&tail[..OPEN_PREFIX.len()]);
+                            rest = &tail[OPEN_PREFIX.len()..];
+                        },
+                    }
+                },
+                _ => {
+                    output.push_str(&tail[..OPEN_PREFIX.len()]);
+                    rest = &tail[OPEN_PREFIX.len()..];
+                },
+            }
+        }
+        output.push_str(rest);
+
+        output
+    }
+
+    fn highlight_block(&self, language: &str, code: &str) -> Option<String> {
+        let syntax = self.syntax_set.find_syntax_by_token(language)?;
+        let theme = self.theme_set.themes.get(&self.theme_name)
+            .or_else(|| self.theme_set.themes.get("InspiredGitHub"))?;
+        let decoded = decode_entities(code);
+
+        highlighted_html_for_string(&decoded, &self.syntax_set, syntax, theme).ok()
+    }
+}
+
+/// Undo the HTML entity escaping the markdown converter applies, since
+/// syntect expects raw source text
+fn decode_entities(code: &str) -> String {
+    code.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use markdown;
+
+    #[test]
+    fn highlights_a_fenced_code_block_produced_by_the_real_markdown_converter() {
+        let converted = markdown::to_html("```rust\nfn main() {}\n```\n");
+        let highlighter = Highlighter::new("InspiredGitHub");
+        let highlighted = highlighter.highlight_code_blocks(&converted);
+
+        assert_ne!(highlighted, converted,
+                   "expected the fenced rust block to be rewritten by syntax highlighting; \
+                    markdown::to_html() may not be emitting the <pre><code class=\"...\"> \
+                    shape highlight_code_blocks() looks for: {:?}", converted);
+    }
+
+    #[test]
+    fn unknown_language_is_left_as_plain_unhighlighted_block() {
+        let highlighter = Highlighter::new("InspiredGitHub");
+        let html = "<pre><code class=\"language-not-a-real-language\">x</code></pre>";
+
+        assert_eq!(highlighter.highlight_code_blocks(html), html);
+    }
+
+    #[test]
+    fn bare_language_class_without_the_language_prefix_is_also_recognized() {
+        let highlighter = Highlighter::new("InspiredGitHub");
+        let html = "<pre><code class=\"rust\">fn main() {}</code></pre>";
+
+        assert_ne!(highlighter.highlight_code_blocks(html), html);
+    }
+}
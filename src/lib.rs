@@ -13,31 +13,130 @@ extern crate mowl;
 extern crate error_chain;
 extern crate uuid;
 extern crate rayon;
+extern crate notify;
+extern crate syntect;
 
 pub mod error;
 pub mod filehash;
+pub mod frontmatter;
+pub mod highlight;
+pub mod links;
+pub mod mime;
+pub mod template;
+pub mod toc;
 
 use error::*;
 use glob::glob;
 use log::LogLevel;
 use markdown::to_html;
+use frontmatter::FrontMatter;
+use highlight::Highlighter;
+use links::LinkIndex;
 
 use iron::prelude::*;
 use iron::status;
-use iron::headers::ContentType;
+use iron::headers::{ContentType, Location};
 
 use std::fs::{self, canonicalize, create_dir_all, File};
-use std::path::{Path, PathBuf, MAIN_SEPARATOR};
+use std::path::{Component, Path, PathBuf, MAIN_SEPARATOR};
 use std::io::prelude::*;
 use std::str;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::channel;
+use std::thread;
+use std::time::Duration;
 use filehash::Filehash;
+use notify::{watcher, DebouncedEvent, RecursiveMode, Watcher};
 use rayon::iter::{ParallelIterator, IntoParallelRefMutIterator};
 
 static SHA_FILE: &str = ".files.sha";
 
+/// Compute the canonical `.html` output path for a markdown `file_path`,
+/// relative to `input_root_dir`. Mirrors the default (non-permalink) output
+/// layout `InputPaths::parse_as_html` writes to, so it can also be used to
+/// build a corpus-wide `LinkIndex` ahead of the actual conversion pass.
+fn default_output_path(file_path: &Path, input_root_dir: &str) -> Result<PathBuf> {
+    let file_buf_n = canonicalize(file_path)?;
+    let file_str_n = file_buf_n.to_str()
+                        .ok_or_else(|| "Unable to stringify canonical normal form of md-file.")?;
+    let input_root_buf_n = canonicalize(&PathBuf::from(input_root_dir))?;
+    let mut input_root_str_n = String::from(
+        input_root_buf_n.to_str()
+        .ok_or_else(|| "Unable to stringify canonical normal form of input root.")?
+    );
+
+    // Add native seperator to avoid getting the wrong path
+    input_root_str_n.push(MAIN_SEPARATOR);
+
+    let output_str = String::from(file_str_n)
+        .replace(input_root_str_n.as_str(), "")
+        .replace(".md", ".html");
+    Ok(PathBuf::from(output_str))
+}
+
+/// The `(input_path, output_path)` pair to index `file` under for wiki-link
+/// resolution, or `None` if `file` is a draft and shouldn't be linkable
+fn link_target(file: &InputPaths, input_root_dir: &str) -> Result<Option<(PathBuf, PathBuf)>> {
+    let front_matter = FrontMatter::read_from_file(&file.path)?;
+    if front_matter.is_draft() {
+        return Ok(None);
+    }
+
+    let output = default_output_path(&file.path, input_root_dir)?;
+    Ok(Some((file.path.clone(), output)))
+}
+
+/// Whether `path` is absolute or contains a `..` component - either of
+/// which would let a front matter `permalink` write outside of
+/// `output_directory` once joined onto it, since `Path::join` discards the
+/// base on an absolute path and `..` walks back up past it
+fn escapes_output_directory(path: &Path) -> bool {
+    path.is_absolute() || path.components().any(|component| component == Component::ParentDir)
+}
+
+/// Whether `output_path` names a markdown source file rather than its
+/// converted HTML, i.e. whether writing it into `output_directory` would
+/// violate the invariant that only converted HTML ever lands there
+fn is_markdown_source(output_path: &Path) -> bool {
+    output_path.extension().and_then(|ext| ext.to_str()) == Some("md")
+}
+
+/// Render an HTML `<li>` list of links for `entries`, one format shared by
+/// the site-wide index and the on-the-fly directory listings `serve` falls
+/// back to when a directory has no `index.html`
+fn render_listing(entries: &[PathBuf]) -> Result<String> {
+    let mut listing = String::new();
+    for entry in entries {
+        listing.push_str(&format!("<li><a href=\"{}\">{}</a></li>\n",
+                                   entry.to_str()
+                                       .ok_or_else(|| "Unable to stringify output path.")?,
+                                   entry.file_name()
+                                       .ok_or_else(|| "Unable to extract file name for path")?
+                                       .to_str().ok_or_else(|| "Unable to stringify output path.")?));
+    }
+
+    Ok(listing)
+}
+
+/// Build an on-the-fly directory listing page for `dir`, reusing the same
+/// list-item format and index template as `Wiki::create_index_tree`
+fn directory_listing(dir: &Path) -> Result<String> {
+    let mut entries = Vec::new();
+    for dir_entry in fs::read_dir(dir)? {
+        entries.push(PathBuf::from(dir_entry?.file_name()));
+    }
+
+    let mut listing = String::from(include_str!("html/index.template.html"));
+    listing.push_str(&render_listing(&entries)?);
+    listing.push_str("</ul>\n</body>\n</html>\n");
+    Ok(listing)
+}
+
 pub struct InputPaths {
     path: PathBuf,
     hash: String,
+    /// Front matter parsed from the leading `---` block, if any
+    front_matter: FrontMatter,
 }
 
 impl InputPaths {
@@ -45,42 +144,55 @@ impl InputPaths {
         InputPaths {
             path: PathBuf::from(path),
             hash: String::new(),
+            front_matter: FrontMatter::default(),
         }
     }
 
     fn parse_as_html(&mut self,
                      input_root_dir: &str,
                      output_dir: &str,
-                     sha_file: &str) -> Result<PathBuf> {
+                     sha_file: &str,
+                     link_index: &LinkIndex,
+                     highlighter: &Highlighter) -> Result<Option<PathBuf>> {
 
         // Open the file and read its content
         let mut f = File::open(&self.path)?;
         let mut buffer = String::new();
         f.read_to_string(&mut buffer)?;
 
+        // Strip off any leading front matter block and expose it on self
+        let (front_matter, body) = FrontMatter::extract(&buffer);
+        self.front_matter = front_matter;
+
+        if self.front_matter.is_draft() {
+            debug!("Skipping draft file: {:?}", self.path);
+            return Ok(None);
+        }
+
+        let default_output = default_output_path(&self.path, input_root_dir)?;
+        let from_dir = default_output.parent().unwrap_or_else(|| Path::new(""));
+        let body = link_index.rewrite(body, from_dir);
+
         // Creating the related HTML file in output_directory
         match self.path.to_str() {
             Some(file_str) => {
-                // Get canonical normal forms of the input path and the recursively
-                // searched directories
-                let file_buf_n = canonicalize(&PathBuf::from(file_str))?;
-                let file_str_n = file_buf_n.to_str()
-                                    .ok_or_else(|| "Unable to stringify canonical normal form of md-file.")?;
-                let input_root_buf_n = canonicalize(&PathBuf::from(input_root_dir))?;
-                let mut input_root_str_n = String::from(
-                    input_root_buf_n.to_str()
-                    .ok_or_else(|| "Unable to stringify canonical normal form of input root.")?
-                );
-
-                // Add native seperator to avoid getting the wrong path
-                input_root_str_n.push(MAIN_SEPARATOR);
-
-                // Reduce the input dir and replace the extension
-                let output_str = String::from(file_str_n)
-                    .replace(input_root_str_n.as_str(), "")
-                    .replace(".md", ".html");
+                // Reduce the input dir and replace the extension, unless the
+                // front matter explicitly overrides the output path
+                let output_str = match self.front_matter.permalink() {
+                    Some(permalink) => permalink.to_owned(),
+                    None => default_output.to_str()
+                        .ok_or_else(|| "Unable to stringify default output path.")?.to_owned(),
+                };
                 let output_path = Path::new(output_str.as_str());
 
+                // A permalink is front-matter-controlled, untrusted input;
+                // reject one that would escape output_directory rather than
+                // silently writing outside of it
+                if self.front_matter.permalink().is_some() && escapes_output_directory(output_path) {
+                    bail!("Refusing to honor a permalink that escapes the output directory: {:?}",
+                          output_path);
+                }
+
                 match output_path.parent() {
                     Some(parent) => {
                         // Creating folder structure if neccessary
@@ -91,6 +203,13 @@ impl InputPaths {
                     None => bail!("Can't get output path parent."),
                 }
 
+                // Invariant: markdown sources are never copied into
+                // output_directory, only their converted HTML is written there
+                if is_markdown_source(output_path) {
+                    bail!("Refusing to write a markdown source into the output directory: {:?}",
+                          output_path);
+                }
+
                 match Filehash::check_hash_currency(sha_file, file_str) {
                     Ok(hash) => {
                         // File hash is up to date, no need to rebuild
@@ -103,11 +222,25 @@ impl InputPaths {
                         info!("Parsing file: {}", file_str);
                         let output_file_path = PathBuf::from(&output_dir)
                                                     .join(output_path);
+                        let highlighted = highlighter.highlight_code_blocks(&to_html(&body));
+                        let (converted, toc_entries) = toc::extract_and_inject(
+                            &highlighted, self.front_matter.toc_max_depth());
+                        let toc_html = toc::render(&toc_entries);
+                        let rendered = template::render(self.front_matter.layout(),
+                                                          &self.front_matter,
+                                                          &[("content", converted.as_str()),
+                                                            ("toc", toc_html.as_str())]);
                         let mut output_file = File::create(&output_file_path)?;
-                        output_file.write_all(to_html(&buffer).as_bytes())?;
+                        output_file.write_all(rendered.as_bytes())?;
+
+                        // Keep the output file's mtime in sync with its
+                        // source so tools like rsync can skip unchanged
+                        // files on incremental deploys
+                        let source_mtime = fs::metadata(&self.path)?.modified()?;
+                        output_file.set_modified(source_mtime)?;
                     },
                 }
-                return Ok(output_path.to_path_buf());
+                return Ok(Some(output_path.to_path_buf()));
             },
             None => bail!("Can not stringfy file path"),
         }
@@ -121,12 +254,21 @@ pub struct Wiki {
     input_paths: Vec<InputPaths>,
     /// The html output paths
     output_paths: Vec<PathBuf>,
+    /// The syntect theme used to highlight fenced code blocks
+    highlight_theme: String,
 }
 
 impl Wiki {
     /// Create a new `Wiki` instance
     pub fn new() -> Self {
-        Self::default()
+        let mut wiki = Self::default();
+        wiki.highlight_theme = String::from("InspiredGitHub");
+        wiki
+    }
+
+    /// Configure the syntect theme used to highlight fenced code blocks
+    pub fn set_highlight_theme(&mut self, theme: &str) {
+        self.highlight_theme = theme.to_owned();
     }
 
     /// Creates a new instance of the processing lib
@@ -188,13 +330,31 @@ impl Wiki {
         let sha_file = sha_file_path.to_str()
                            .ok_or_else(|| "Unable to stringify the sha file path.")?;
 
+        // Build a corpus-wide index of [[wiki links]] before converting any
+        // individual file, since resolution needs to see every input path.
+        // Draft pages are excluded so links never resolve to a page that
+        // won't actually be written.
+        let link_pairs: Result<Vec<Option<(PathBuf, PathBuf)>>> = self.input_paths.iter()
+            .map(|file| link_target(file, input_root_dir))
+            .collect();
+        let link_pairs: Vec<(PathBuf, PathBuf)> = link_pairs?.into_iter().filter_map(|pair| pair).collect();
+        let link_index = LinkIndex::build(link_pairs.iter()
+            .map(|&(ref input, ref output)| (input.as_path(), output.as_path())));
+
+        // Loading the syntax/theme sets is the expensive part of
+        // highlighting, so do it once up front rather than per file
+        let highlighter = Highlighter::new(&self.highlight_theme);
+
         // Iterate over all available input_paths
         self.output_paths = self.input_paths.par_iter_mut()
                                             .filter_map(|ref mut file|
                                                         file.parse_as_html(input_root_dir,
                                                                            output_directory,
-                                                                           sha_file)
+                                                                           sha_file,
+                                                                           &link_index,
+                                                                           &highlighter)
                                                         .ok())
+                                            .filter_map(|maybe_path| maybe_path)
                                             .collect();
 
         Filehash::write_file_hash(&mut self.input_paths, sha_file)?;
@@ -204,38 +364,53 @@ impl Wiki {
 
     /// Creates an index.html with simple tree structure view when no index.md was seen
     pub fn create_index_tree(&self, output_directory: &str) -> Result<()> {
+        self.write_index_tree(output_directory, false)
+    }
+
+    /// Write the site-wide index.html. When `force` is `true`, an existing
+    /// index is overwritten so a live-reload rebuild picks up pages that
+    /// were added or removed while serving; otherwise an existing
+    /// index.html (e.g. a hand-written one) is left untouched.
+    fn write_index_tree(&self, output_directory: &str, force: bool) -> Result<()> {
         let index_path = Path::new(output_directory).join("index.html");
-        if !index_path.exists() {
+        if force || !index_path.exists() {
             info!("Creating index.html at {}",
                   index_path.to_str().ok_or_else(|| "Unable to stringify index path.")?);
             let mut index_file = File::create(index_path)?;
             let mut index_str = String::from(include_str!("html/index.template.html"));
-            for output_path in &self.output_paths {
-                index_str.push_str(format!("<li><a href=\"{}\">{}</a></li>\n",
-                                           output_path.to_str()
-                                               .ok_or_else(|| "Unable to stringify output path.")?,
-                                           output_path.file_name()
-                                               .ok_or_else(|| "Unable to extract file name for path")?
-                                               .to_str().ok_or_else(|| "Unable to stringify output path.")?)
-                                   .as_str());
-            }
+            index_str.push_str(&render_listing(&self.output_paths)?);
+            index_str.push_str("</ul>\n</body>\n</html>\n");
             index_file.write_all(index_str.as_bytes())?;
         }
 
         Ok(())
     }
 
-    /// Create an HTTP server serving the generated files
-    pub fn serve(&self, output_directory: &str) -> Result<()> {
+    /// Create an HTTP server serving the generated files, watching
+    /// `input_root_dir` for changes and rebuilding affected pages
+    /// automatically so editing a `.md` file refreshes the site without
+    /// restarting the binary
+    pub fn serve(self, input_root_dir: &str, output_directory: &str) -> Result<()> {
         // Create a default listening address
         let addr = "localhost:5000";
         info!("Listening on {}", addr);
 
+        let wiki = Arc::new(Mutex::new(self));
+        Self::watch_and_rebuild(Arc::clone(&wiki), input_root_dir, output_directory);
+
         // Moving the data into the closure
         let output_directory_string = output_directory.to_owned();
 
         // Create a new iron instance
         Iron::new(move |request: &mut Request| {
+                // Cheap first check before the filesystem is touched at all:
+                // reject `..` and absolute segments outright
+                if request.url.path().iter().any(|part| *part == ".." || Path::new(part).is_absolute()) {
+                    return Ok(Response::with((ContentType::html().0,
+                                              status::Forbidden,
+                                              include_str!("html/403.html"))));
+                }
+
                 // The owned path needs to created from the cloned string
                 let mut path = PathBuf::from(output_directory_string.clone());
 
@@ -244,34 +419,154 @@ impl Wiki {
                     path.push(part);
                 }
 
-                // Could use some security validation for the path here.
+                // Canonicalize both the candidate path and the output root
+                // and reject anything that resolves outside of it (e.g. via
+                // symlinks the cheap check above can't catch)
+                let canonical_root = match canonicalize(&output_directory_string) {
+                    Ok(v) => v,
+                    Err(_) => return Ok(Response::with((ContentType::html().0,
+                                                        status::InternalServerError,
+                                                        include_str!("html/500.html")))),
+                };
+                let mut path = match canonicalize(&path) {
+                    Ok(v) => v,
+                    Err(_) => return Ok(Response::with((ContentType::html().0,
+                                                        status::NotFound,
+                                                        include_str!("html/404.html")))),
+                };
+                if !path.starts_with(&canonical_root) {
+                    warn!("Rejected path traversal attempt for '{:?}'", path);
+                    return Ok(Response::with((ContentType::html().0,
+                                              status::Forbidden,
+                                              include_str!("html/403.html"))));
+                }
 
-                // Use a default page for the middleware
+                // Use a default page for the middleware, or generate a
+                // directory listing on the fly when there's no index.html
                 if path.is_dir() {
-                    path.push("index.html");
+                    // A directory request with no trailing slash (e.g.
+                    // "/docs") would otherwise serve a listing whose
+                    // relative links resolve one level too high; redirect
+                    // to the slash-terminated form instead
+                    let request_segments = request.url.path();
+                    if request_segments.last().map_or(false, |segment| !segment.is_empty()) {
+                        let mut location = String::from("/");
+                        location.push_str(&request_segments.join("/"));
+                        location.push('/');
+                        let mut redirect = Response::with(status::MovedPermanently);
+                        redirect.headers.set(Location(location));
+                        return Ok(redirect);
+                    }
+
+                    let index_path = path.join("index.html");
+                    if index_path.exists() {
+                        path = index_path;
+                    } else {
+                        return match directory_listing(&path) {
+                            Ok(listing) => Ok(Response::with((ContentType::html().0,
+                                                              status::Ok,
+                                                              listing))),
+                            Err(_) => Ok(Response::with((ContentType::html().0,
+                                                         status::InternalServerError,
+                                                         include_str!("html/500.html")))),
+                        };
+                    }
                 }
 
-                let mut f = match File::open(path) {
+                // Content type is determined from the requested file's extension
+                let content_type = mime::detect(&path);
+
+                let buffer = match fs::read(&path) {
                     Ok(v) => v,
                     _ => return Ok(Response::with((ContentType::html().0,
                                                    status::NotFound,
                                                    include_str!("html/404.html")))),
                 };
 
-                let mut buffer = String::new();
-                match f.read_to_string(&mut buffer) {
-                    Ok(v) => v,
-                    _ => return Ok(Response::with((ContentType::html().0,
-                                                   status::InternalServerError,
-                                                   include_str!("html/500.html")))),
-                };
-
-                // Content type needs to be determined from the file rather
-                // than assuming html
-                Ok(Response::with((ContentType::html().0, status::Ok, buffer)))
+                Ok(Response::with((content_type, status::Ok, buffer)))
 
             }).http(addr)?;
 
         Ok(())
     }
+
+    /// Spawn a debounced filesystem watcher on `input_root_dir` that
+    /// rebuilds `wiki` whenever a markdown file is created, modified or
+    /// removed. Rebuilds stay cheap since `read_content_from_current_paths`
+    /// already skips files whose hash hasn't changed.
+    fn watch_and_rebuild(wiki: Arc<Mutex<Self>>, input_root_dir: &str, output_directory: &str) {
+        let input_root_dir = input_root_dir.to_owned();
+        let output_directory = output_directory.to_owned();
+
+        thread::spawn(move || {
+            let (tx, rx) = channel();
+            let mut watcher = match watcher(tx, Duration::from_millis(300)) {
+                Ok(watcher) => watcher,
+                Err(e) => {
+                    error!("Unable to start the live-reload file watcher: {}", e);
+                    return;
+                },
+            };
+
+            if let Err(e) = watcher.watch(&input_root_dir, RecursiveMode::Recursive) {
+                error!("Unable to watch '{}': {}", input_root_dir, e);
+                return;
+            }
+
+            loop {
+                match rx.recv() {
+                    Ok(DebouncedEvent::Create(_)) |
+                    Ok(DebouncedEvent::Write(_)) |
+                    Ok(DebouncedEvent::Remove(_)) |
+                    Ok(DebouncedEvent::Rename(_, _)) => {
+                        info!("Change detected under '{}', rebuilding.", input_root_dir);
+                        let mut wiki = match wiki.lock() {
+                            Ok(wiki) => wiki,
+                            Err(_) => continue,
+                        };
+
+                        if let Err(e) = wiki.read_from_directory(&input_root_dir)
+                            .and_then(|_| wiki.read_content_from_current_paths(&input_root_dir,
+                                                                               &output_directory))
+                            .and_then(|_| wiki.write_index_tree(&output_directory, true)) {
+                            error!("Live rebuild failed: {}", e);
+                        }
+                    },
+                    Ok(_) => {},
+                    Err(e) => {
+                        error!("File watcher disconnected: {}", e);
+                        break;
+                    },
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn markdown_output_path_is_rejected() {
+        assert!(is_markdown_source(Path::new("notes/Example.md")));
+    }
+
+    #[test]
+    fn html_output_path_is_accepted() {
+        assert!(!is_markdown_source(Path::new("notes/Example.html")));
+    }
+
+    #[test]
+    fn absolute_or_parent_escaping_permalinks_are_rejected() {
+        assert!(escapes_output_directory(Path::new("/etc/whatever.html")));
+        assert!(escapes_output_directory(Path::new("../../etc/whatever.html")));
+        assert!(escapes_output_directory(Path::new("notes/../../../whatever.html")));
+    }
+
+    #[test]
+    fn ordinary_relative_permalinks_are_accepted() {
+        assert!(!escapes_output_directory(Path::new("notes/example.html")));
+        assert!(!escapes_output_directory(Path::new("example.html")));
+    }
 }
@@ -0,0 +1,180 @@
+//! Resolution of wiki-style `[[Target]]` / `[[Target|Label]]` internal
+//! links against the set of known input paths
+//!
+//! Because resolution needs to see every file up front, callers build a
+//! `LinkIndex` once for the whole corpus before converting any individual
+//! file, rather than resolving links file-by-file.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Maps a lowercased file stem to every output path sharing that stem, plus
+/// a lowercased, extension-less relative path to its exact output path for
+/// targets that qualify a directory (e.g. `[[subdir/Page]]`)
+#[derive(Default)]
+pub struct LinkIndex {
+    by_stem: HashMap<String, Vec<PathBuf>>,
+    by_relative_path: HashMap<String, PathBuf>,
+}
+
+/// Normalize a path-like string for lookup: forward slashes, lowercase,
+/// extension stripped
+fn normalize(path: &str) -> String {
+    let path = path.replace('\\', "/").to_lowercase();
+    match path.rfind('.') {
+        Some(dot) if !path[dot..].contains('/') => path[..dot].to_owned(),
+        _ => path,
+    }
+}
+
+/// Render `output_path` (relative to the site root) as an absolute,
+/// `/`-rooted href. `Wiki::serve` serves `output_directory` at the site
+/// root, so this resolves correctly regardless of which directory the
+/// linking page itself lives in - unlike a bare root-relative path, which
+/// the browser would instead resolve against the linking page's own
+/// directory
+fn root_relative_href(output_path: &Path) -> String {
+    format!("/{}", output_path.to_str().unwrap_or_default().replace('\\', "/"))
+}
+
+impl LinkIndex {
+    /// Build an index from the `(input_path, output_path)` pairs of every
+    /// known (non-draft) markdown file
+    pub fn build<'a, I>(paths: I) -> Self
+        where I: IntoIterator<Item = (&'a Path, &'a Path)>
+    {
+        let mut by_stem: HashMap<String, Vec<PathBuf>> = HashMap::new();
+        let mut by_relative_path: HashMap<String, PathBuf> = HashMap::new();
+
+        for (input_path, output_path) in paths {
+            if let Some(stem) = input_path.file_stem().and_then(|s| s.to_str()) {
+                by_stem.entry(stem.to_lowercase())
+                       .or_insert_with(Vec::new)
+                       .push(output_path.to_path_buf());
+            }
+
+            if let Some(relative) = output_path.to_str() {
+                by_relative_path.insert(normalize(relative), output_path.to_path_buf());
+            }
+        }
+
+        LinkIndex { by_stem: by_stem, by_relative_path: by_relative_path }
+    }
+
+    /// Resolve `target` (a `[[Target]]` token, possibly a relative path)
+    /// against the index, preferring a match in the same directory as
+    /// `from_dir`
+    pub fn resolve(&self, target: &str, from_dir: &Path) -> Option<PathBuf> {
+        let normalized_target = normalize(target);
+
+        // A qualified relative path (e.g. "subdir/Page") names an exact
+        // file - honor the directory component rather than falling back to
+        // a same-stem match that could resolve to the wrong page
+        if normalized_target.contains('/') {
+            return self.by_relative_path.get(&normalized_target).cloned();
+        }
+
+        let candidates = self.by_stem.get(&normalized_target)?;
+
+        candidates.iter()
+            .find(|candidate| candidate.parent() == Some(from_dir))
+            .or_else(|| candidates.first())
+            .cloned()
+    }
+
+    /// Rewrite every `[[Target]]` / `[[Target|Label]]` token in `body` into
+    /// a real `<a href="...">` link pointing at the resolved output path,
+    /// or a `class="broken-link"` span if `target` can't be resolved
+    pub fn rewrite(&self, body: &str, from_dir: &Path) -> String {
+        let mut output = String::with_capacity(body.len());
+        let mut rest = body;
+
+        while let Some(start) = rest.find("[[") {
+            output.push_str(&rest[..start]);
+            let after = &rest[start + 2..];
+
+            match after.find("]]") {
+                Some(end) => {
+                    let token = &after[..end];
+                    let (target, label) = match token.find('|') {
+                        Some(sep) => (&token[..sep], &token[sep + 1..]),
+                        None => (token, token),
+                    };
+
+                    match self.resolve(target, from_dir) {
+                        Some(resolved) => {
+                            output.push_str(&format!("<a href=\"{}\">{}</a>",
+                                                      root_relative_href(&resolved), label));
+                        },
+                        None => {
+                            warn!("Unresolved wiki link target: '{}'", target);
+                            output.push_str(&format!("<span class=\"broken-link\">{}</span>", label));
+                        },
+                    }
+
+                    rest = &after[end + 2..];
+                },
+                None => {
+                    output.push_str("[[");
+                    rest = after;
+                },
+            }
+        }
+        output.push_str(rest);
+
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_bare_target_by_stem() {
+        let pairs = vec![(PathBuf::from("notes/Example.md"), PathBuf::from("notes/Example.html"))];
+        let index = LinkIndex::build(pairs.iter().map(|&(ref i, ref o)| (i.as_path(), o.as_path())));
+
+        let resolved = index.resolve("Example", Path::new("other"));
+        assert_eq!(resolved, Some(PathBuf::from("notes/Example.html")));
+    }
+
+    #[test]
+    fn resolves_qualified_relative_target_exactly() {
+        let pairs = vec![(PathBuf::from("a/Page.md"), PathBuf::from("a/Page.html")),
+                         (PathBuf::from("b/Page.md"), PathBuf::from("b/Page.html"))];
+        let index = LinkIndex::build(pairs.iter().map(|&(ref i, ref o)| (i.as_path(), o.as_path())));
+
+        assert_eq!(index.resolve("b/Page", Path::new(".")), Some(PathBuf::from("b/Page.html")));
+        assert_eq!(index.resolve("a/Page", Path::new(".")), Some(PathBuf::from("a/Page.html")));
+    }
+
+    #[test]
+    fn unresolved_target_renders_broken_link_span() {
+        let index = LinkIndex::default();
+        let rewritten = index.rewrite("See [[Missing|the missing page]].", Path::new("."));
+        assert_eq!(rewritten, "See <span class=\"broken-link\">the missing page</span>.");
+    }
+
+    #[test]
+    fn resolved_target_rewrites_to_anchor_tag() {
+        let pairs = vec![(PathBuf::from("Example.md"), PathBuf::from("Example.html"))];
+        let index = LinkIndex::build(pairs.iter().map(|&(ref i, ref o)| (i.as_path(), o.as_path())));
+
+        let rewritten = index.rewrite("See [[Example]].", Path::new("."));
+        assert_eq!(rewritten, "See <a href=\"/Example.html\">Example</a>.");
+    }
+
+    #[test]
+    fn rewritten_links_are_rooted_so_they_resolve_from_any_directory() {
+        let pairs = vec![(PathBuf::from("docs/a.md"), PathBuf::from("docs/a.html")),
+                         (PathBuf::from("other/page.md"), PathBuf::from("other/page.html"))];
+        let index = LinkIndex::build(pairs.iter().map(|&(ref i, ref o)| (i.as_path(), o.as_path())));
+
+        // A link from docs/a.md to other/page.md must not render as a path
+        // relative to docs/, or the browser would resolve it to the wrong,
+        // nonexistent docs/other/page.html
+        let rewritten = index.rewrite("See [[page]].", Path::new("docs"));
+        assert_eq!(rewritten, "See <a href=\"/other/page.html\">page</a>.");
+    }
+}
@@ -1,67 +1,49 @@
 //! # wiki
 #![deny(missing_docs)]
 
-extern crate markdown;
-extern crate glob;
+extern crate wiki;
 #[macro_use]
 extern crate clap;
 #[macro_use]
 extern crate log;
-extern crate mowl;
-
-#[macro_use]
-pub mod error;
-pub mod processing;
 
 use clap::Arg;
-use processing::Processing;
-use error::{WikiError, ErrorType};
+use wiki::Wiki;
 use std::process::exit;
 
 static ARG_INPUT_DIRECTORY: &'static str = "INPUT";
 static ARG_OUTPUT_DIRECTORY: &'static str = "output-directory";
+static ARG_HIGHLIGHT_THEME: &'static str = "highlight-theme";
 static DEFAULT_HTML_DIR: &'static str = "output";
+static DEFAULT_HIGHLIGHT_THEME: &'static str = "InspiredGitHub";
 
-fn error_and_exit(error: WikiError) {
-    error!("{}", error);
-    exit(1);
-}
-
-fn run(mut retval: WikiError) -> WikiError {
+fn run() -> wiki::error::Result<()> {
     // Parse the given arguments
     let matches = app_from_crate!()
         .arg(Arg::from_usage("-o --output-directory=[PATH] 'The directory where the HTML output is generated.'"))
+        .arg(Arg::from_usage("-t --highlight-theme=[THEME] 'The syntect theme used to highlight fenced code blocks.'"))
         .arg(Arg::from_usage("<INPUT>                      'The directory containing the markdown files to use.'"))
         .get_matches();
 
     let md_dir = matches.value_of(ARG_INPUT_DIRECTORY).unwrap();
     let html_dir = matches.value_of(ARG_OUTPUT_DIRECTORY).unwrap_or(DEFAULT_HTML_DIR);
+    let highlight_theme = matches.value_of(ARG_HIGHLIGHT_THEME).unwrap_or(DEFAULT_HIGHLIGHT_THEME);
 
-    // Init logger crate
-    match mowl::init() {
-        Ok(_) => debug!("Mowl logging initiated."),
-        Err(_) => {
-            retval.code = ErrorType::InitFailure;
-            return retval;
-        },
-    }
-
-    // This can be deleted when html_dir is used further
-    debug!("Output path: {}", html_dir);
+    let mut wiki = Wiki::new();
+    wiki.set_highlight_theme(highlight_theme);
+    wiki.init_logging(log::LogLevel::Info)?;
 
-    // Do first processing steps
-    let mut processing = Processing::default();
+    wiki.read_from_directory(&md_dir)?;
+    wiki.list_current_input_paths();
+    wiki.read_content_from_current_paths(&md_dir, &html_dir)?;
+    wiki.create_index_tree(&html_dir)?;
 
-    return_if_not_ok!(processing.read_from_directory(&md_dir));
-    processing.list_current_paths();
-    return_if_not_ok!(processing.read_content_from_current_paths());
-    return retval;
+    Ok(())
 }
 
 fn main() {
-    let mut retval = WikiError::default();
-    retval = run(retval);
-    if retval.code != ErrorType::Ok {
-        error_and_exit(retval);
+    if let Err(e) = run() {
+        error!("{}", e);
+        exit(1);
     }
 }
@@ -0,0 +1,39 @@
+//! Best-effort `Content-Type` detection for files served by `Wiki::serve`
+
+use std::path::Path;
+use iron::mime::{Mime, TopLevel, SubLevel};
+
+/// Guess the `Content-Type` for `path` from its extension, falling back to
+/// `application/octet-stream` for anything unrecognized
+pub fn detect(path: &Path) -> Mime {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("html") | Some("htm") => Mime(TopLevel::Text, SubLevel::Html, vec![]),
+        Some("css") => Mime(TopLevel::Text, SubLevel::Css, vec![]),
+        Some("js") => Mime(TopLevel::Application, SubLevel::Javascript, vec![]),
+        Some("png") => Mime(TopLevel::Image, SubLevel::Png, vec![]),
+        Some("jpg") | Some("jpeg") => Mime(TopLevel::Image, SubLevel::Jpeg, vec![]),
+        Some("svg") => Mime(TopLevel::Image, SubLevel::Ext("svg+xml".to_owned()), vec![]),
+        _ => Mime(TopLevel::Application, SubLevel::Ext("octet-stream".to_owned()), vec![]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_known_extensions() {
+        assert_eq!(detect(Path::new("index.html")), Mime(TopLevel::Text, SubLevel::Html, vec![]));
+        assert_eq!(detect(Path::new("style.css")), Mime(TopLevel::Text, SubLevel::Css, vec![]));
+        assert_eq!(detect(Path::new("app.js")),
+                   Mime(TopLevel::Application, SubLevel::Javascript, vec![]));
+    }
+
+    #[test]
+    fn falls_back_to_octet_stream_for_unknown_or_missing_extensions() {
+        assert_eq!(detect(Path::new("data.bin")),
+                   Mime(TopLevel::Application, SubLevel::Ext("octet-stream".to_owned()), vec![]));
+        assert_eq!(detect(Path::new("no_extension")),
+                   Mime(TopLevel::Application, SubLevel::Ext("octet-stream".to_owned()), vec![]));
+    }
+}
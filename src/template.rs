@@ -0,0 +1,85 @@
+//! Layout templating: substitutes `{{ placeholder }}` tokens in an HTML
+//! template with values taken from a file's front matter plus whatever
+//! generated substitutions (converted body, table of contents, ...) the
+//! caller supplies
+
+use frontmatter::FrontMatter;
+
+static DEFAULT_TEMPLATE: &str = include_str!("html/default.template.html");
+static PAGE_TEMPLATE: &str = include_str!("html/page.template.html");
+
+/// Render `substitutions` (e.g. `("content", ...)`, `("toc", ...)`) and
+/// every key found in `front_matter` (e.g. `{{ title }}`) into the layout
+/// named by `layout`. Unknown layout names fall back to the default layout.
+/// Since front matter is optional, any placeholder left unresolved (e.g.
+/// `{{ title }}` in a file with no front matter block at all) is stripped
+/// rather than shipped as literal, unrendered template syntax.
+pub fn render(layout: &str, front_matter: &FrontMatter, substitutions: &[(&str, &str)]) -> String {
+    let template = match layout {
+        "page" => PAGE_TEMPLATE,
+        _ => DEFAULT_TEMPLATE,
+    };
+
+    let mut rendered = template.to_owned();
+    for &(key, value) in substitutions {
+        rendered = rendered.replace(&format!("{{{{ {} }}}}", key), value);
+    }
+    for (key, value) in front_matter.iter() {
+        rendered = rendered.replace(&format!("{{{{ {} }}}}", key), value);
+    }
+
+    strip_unresolved_placeholders(&rendered)
+}
+
+/// Remove any `{{ ... }}` placeholder left over after known substitutions
+/// have been applied
+fn strip_unresolved_placeholders(rendered: &str) -> String {
+    let mut output = String::with_capacity(rendered.len());
+    let mut rest = rendered;
+
+    while let Some(start) = rest.find("{{") {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+
+        match after.find("}}") {
+            Some(end) => rest = &after[end + 2..],
+            None => {
+                output.push_str("{{");
+                rest = after;
+            },
+        }
+    }
+    output.push_str(rest);
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_title_placeholder_is_stripped_not_left_literal() {
+        let front_matter = FrontMatter::default();
+        let rendered = render("default", &front_matter, &[("content", "Body"), ("toc", "")]);
+
+        assert!(!rendered.contains("{{"));
+        assert!(rendered.contains("<title></title>"));
+    }
+
+    #[test]
+    fn front_matter_title_is_substituted() {
+        let (front_matter, _) = FrontMatter::extract("---\ntitle: Hi\n---\n");
+        let rendered = render("default", &front_matter, &[("content", "Body"), ("toc", "")]);
+
+        assert!(rendered.contains("<title>Hi</title>"));
+    }
+
+    #[test]
+    fn unknown_layout_falls_back_to_the_default_template() {
+        let front_matter = FrontMatter::default();
+        let rendered = render("not-a-real-layout", &front_matter, &[("content", "Body"), ("toc", "")]);
+
+        assert!(!rendered.contains("<article>"));
+    }
+}
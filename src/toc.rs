@@ -0,0 +1,192 @@
+//! Table of contents generation: scans the headings of converted markdown
+//! HTML, assigns each one a unique anchor `id` and collects an ordered list
+//! of entries that can be rendered into a nested `<ul>`
+
+use std::collections::{HashMap, HashSet};
+
+/// A single table of contents entry: heading level, visible title and the
+/// anchor `id` assigned to it
+pub type TocEntry = (u8, String, String);
+
+/// Turn `title` into a URL-safe anchor: lowercase, whitespace becomes `-`,
+/// anything that isn't alphanumeric or `-` is stripped
+pub fn slugify(title: &str) -> String {
+    let mut slug = String::new();
+    let mut last_was_dash = false;
+
+    for c in title.trim().chars() {
+        if c.is_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+            last_was_dash = false;
+        } else if (c.is_whitespace() || c == '-') && !last_was_dash && !slug.is_empty() {
+            slug.push('-');
+            last_was_dash = true;
+        }
+    }
+
+    while slug.ends_with('-') {
+        slug.pop();
+    }
+
+    slug
+}
+
+/// Scan `html` for `<h1>`-`<h6>` headings, assign each a unique `id`
+/// (de-duplicating collisions with a `-1`, `-2`, ... suffix), inject that
+/// `id` back into the heading tag and return the rewritten HTML together
+/// with the ordered list of `(level, title, id)` entries up to `max_depth`
+pub fn extract_and_inject(html: &str, max_depth: u8) -> (String, Vec<TocEntry>) {
+    let mut output = String::with_capacity(html.len());
+    let mut entries = Vec::new();
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let mut used: HashSet<String> = HashSet::new();
+    let mut rest = html;
+
+    while let Some(start) = rest.find("<h") {
+        output.push_str(&rest[..start]);
+        let after_open = &rest[start..];
+
+        let level = after_open.as_bytes().get(2).cloned().and_then(|b| {
+            if (b'1'..=b'6').contains(&b) { Some(b - b'0') } else { None }
+        });
+
+        let level = match level {
+            Some(level) => level,
+            None => {
+                output.push_str(&after_open[..2]);
+                rest = &after_open[2..];
+                continue;
+            },
+        };
+
+        let open_tag = format!("<h{}>", level);
+        let close_tag = format!("</h{}>", level);
+
+        match after_open.find(&close_tag) {
+            Some(close_at) if close_at >= open_tag.len() => {
+                let title = &after_open[open_tag.len()..close_at];
+                let base_slug = slugify(title);
+                let count = seen.entry(base_slug.clone()).or_insert(0);
+                let mut id = if *count == 0 { base_slug.clone() } else { format!("{}-{}", base_slug, count) };
+                while used.contains(&id) {
+                    *count += 1;
+                    id = format!("{}-{}", base_slug, count);
+                }
+                *count += 1;
+                used.insert(id.clone());
+
+                if level <= max_depth {
+                    entries.push((level, title.to_string(), id.clone()));
+                }
+
+                output.push_str(&format!("<h{} id=\"{}\">{}</h{}>", level, id, title, level));
+                rest = &after_open[close_at + close_tag.len()..];
+            },
+            _ => {
+                output.push_str(&after_open[..2]);
+                rest = &after_open[2..];
+            },
+        }
+    }
+    output.push_str(rest);
+
+    (output, entries)
+}
+
+/// Render an ordered list of toc entries into a nested `<ul>` reflecting
+/// heading depth
+pub fn render(entries: &[TocEntry]) -> String {
+    if entries.is_empty() {
+        return String::new();
+    }
+
+    let base_level = entries.iter().map(|&(level, _, _)| level).min().unwrap_or(1);
+    let mut html = String::from("<ul>\n");
+    let mut current_level = base_level;
+
+    for &(level, ref title, ref id) in entries {
+        while current_level < level {
+            html.push_str("<ul>\n");
+            current_level += 1;
+        }
+        while current_level > level {
+            html.push_str("</ul>\n");
+            current_level -= 1;
+        }
+        html.push_str(&format!("<li><a href=\"#{}\">{}</a></li>\n", id, title));
+    }
+
+    while current_level > base_level {
+        html.push_str("</ul>\n");
+        current_level -= 1;
+    }
+    html.push_str("</ul>\n");
+
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slugify_lowercases_and_dashes_whitespace() {
+        assert_eq!(slugify("Hello World"), "hello-world");
+    }
+
+    #[test]
+    fn slugify_strips_punctuation_and_collapses_runs_of_separators() {
+        assert_eq!(slugify("Q&A: What -- now?"), "qa-what-now");
+    }
+
+    #[test]
+    fn slugify_of_an_empty_or_all_punctuation_title_is_empty() {
+        assert_eq!(slugify("   "), "");
+        assert_eq!(slugify("---"), "");
+    }
+
+    #[test]
+    fn extract_and_inject_assigns_ids_and_collects_entries() {
+        let html = "<p>intro</p><h1>Title</h1><p>body</p><h2>Sub</h2>";
+        let (rewritten, entries) = extract_and_inject(html, 6);
+
+        assert_eq!(rewritten,
+                   "<p>intro</p><h1 id=\"title\">Title</h1><p>body</p><h2 id=\"sub\">Sub</h2>");
+        assert_eq!(entries, vec![(1, "Title".to_owned(), "title".to_owned()),
+                                  (2, "Sub".to_owned(), "sub".to_owned())]);
+    }
+
+    #[test]
+    fn extract_and_inject_de_duplicates_colliding_slugs() {
+        let html = "<h2>Intro</h2><h2>Intro</h2>";
+        let (rewritten, entries) = extract_and_inject(html, 6);
+
+        assert_eq!(rewritten, "<h2 id=\"intro\">Intro</h2><h2 id=\"intro-1\">Intro</h2>");
+        assert_eq!(entries, vec![(2, "Intro".to_owned(), "intro".to_owned()),
+                                  (2, "Intro".to_owned(), "intro-1".to_owned())]);
+    }
+
+    #[test]
+    fn extract_and_inject_excludes_headings_below_max_depth_from_entries() {
+        let html = "<h1>Title</h1><h3>Deep</h3>";
+        let (rewritten, entries) = extract_and_inject(html, 2);
+
+        assert_eq!(rewritten, "<h1 id=\"title\">Title</h1><h3 id=\"deep\">Deep</h3>");
+        assert_eq!(entries, vec![(1, "Title".to_owned(), "title".to_owned())]);
+    }
+
+    #[test]
+    fn render_nests_lists_by_heading_level() {
+        let entries = vec![(1, "Title".to_owned(), "title".to_owned()),
+                            (2, "Sub".to_owned(), "sub".to_owned())];
+
+        assert_eq!(render(&entries),
+                   "<ul>\n<li><a href=\"#title\">Title</a></li>\n<ul>\n\
+                    <li><a href=\"#sub\">Sub</a></li>\n</ul>\n</ul>\n");
+    }
+
+    #[test]
+    fn render_of_no_entries_is_empty() {
+        assert_eq!(render(&[]), "");
+    }
+}